@@ -1,11 +1,139 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use std::io::{BufRead, BufReader};
+use std::net::{TcpListener, TcpStream};
 use std::path::PathBuf;
-use std::process::{Child, Command};
-use tauri::Manager;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager};
+use tauri_plugin_dialog::DialogExt;
+use tauri_plugin_shell::process::{CommandChild, CommandEvent};
+use tauri_plugin_shell::ShellExt;
 
-fn spawn_backend() -> Option<Child> {
-    // Dev mode: run local Python backend script.
+const READY_INITIAL_BACKOFF: Duration = Duration::from_millis(50);
+const READY_MAX_BACKOFF: Duration = Duration::from_secs(2);
+const READY_TOTAL_TIMEOUT: Duration = Duration::from_secs(30);
+
+const SUPERVISOR_POLL_INTERVAL: Duration = Duration::from_millis(250);
+const MAX_RESTARTS_PER_WINDOW: usize = 5;
+const RESTART_WINDOW: Duration = Duration::from_secs(60);
+const RESTART_INITIAL_BACKOFF: Duration = Duration::from_millis(200);
+const RESTART_MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// A spawned backend, whether launched directly (dev mode) or through
+/// Tauri's sidecar resolver (packaged mode). Both variants expose the same
+/// liveness/kill surface so the rest of the file doesn't need to care which
+/// one it's holding.
+enum BackendChild {
+    Std(Child),
+    Sidecar(Option<CommandChild>),
+}
+
+impl BackendChild {
+    fn has_exited(&mut self, sidecar_exited: &AtomicBool) -> bool {
+        match self {
+            BackendChild::Std(child) => matches!(child.try_wait(), Ok(Some(_))),
+            BackendChild::Sidecar(_) => sidecar_exited.load(Ordering::SeqCst),
+        }
+    }
+
+    fn kill(&mut self) {
+        match self {
+            BackendChild::Std(child) => {
+                let _ = child.kill();
+            }
+            BackendChild::Sidecar(child) => {
+                if let Some(child) = child.take() {
+                    let _ = child.kill();
+                }
+            }
+        }
+    }
+}
+
+/// A running backend plus the plumbing needed to supervise it: a one-shot
+/// relay of its first stdout line (used for the port handshake — every
+/// later line is only printed, not queued) and, for sidecar children, a
+/// flag flipped by the event-forwarding thread on termination.
+struct BackendProcess {
+    child: BackendChild,
+    first_stdout_line: Receiver<String>,
+    sidecar_exited: Arc<AtomicBool>,
+}
+
+impl BackendProcess {
+    fn has_exited(&mut self) -> bool {
+        self.child.has_exited(&self.sidecar_exited)
+    }
+
+    fn kill(&mut self) {
+        self.child.kill();
+    }
+}
+
+/// Shared handle to the running backend. The supervisor thread and the
+/// shutdown path both go through this so there's one source of truth for
+/// "is a backend currently running, and which one".
+type BackendHandle = Arc<Mutex<Option<BackendProcess>>>;
+
+/// Binds an ephemeral port on loopback and immediately releases it so the
+/// backend can bind it in turn. There's an inherent TOCTOU race here; the
+/// stdout handshake in `read_announced_port` is the real source of truth.
+fn pick_free_port() -> u16 {
+    TcpListener::bind(("127.0.0.1", 0))
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .unwrap_or(8787)
+}
+
+/// Prints every line read from `reader` for local logging, relaying only
+/// the first one through `tx` (the handshake in `read_announced_port` only
+/// ever reads once). Runs until the stream closes. `tx` is only `Some` for
+/// that first send — dropping it afterwards means later lines are just
+/// printed rather than queued into a channel nobody drains for the rest of
+/// the backend's lifetime.
+fn forward_stdout_lines(mut reader: impl BufRead, mut tx: Option<mpsc::Sender<String>>) {
+    let mut line = String::new();
+    while reader.read_line(&mut line).unwrap_or(0) > 0 {
+        print!("{line}");
+        if let Some(sender) = tx.take() {
+            let _ = sender.send(std::mem::take(&mut line));
+        }
+        line.clear();
+    }
+}
+
+/// Resolves the sidecar command for packaged mode. Under an AppImage,
+/// `current_exe()`-relative lookups (and Tauri's own default resolution,
+/// which is built on the same assumption) point inside the mounted
+/// squashfs rather than at the real `usr/bin` the runtime extracts, so we
+/// detect that environment via the `APPDIR`/`APPIMAGE` variables the
+/// AppImage runtime sets and resolve `pi-backend` relative to `$APPDIR`
+/// directly. Everywhere else — other platforms, or a non-AppImage Linux
+/// build — we fall back to the shell plugin's normal sidecar resolution.
+fn resolve_sidecar_command(app: &tauri::AppHandle) -> Option<tauri_plugin_shell::process::Command> {
+    let shell = app.shell();
+
+    #[cfg(target_os = "linux")]
+    {
+        if std::env::var_os("APPIMAGE").is_some() {
+            if let Ok(appdir) = std::env::var("APPDIR") {
+                let candidate = PathBuf::from(appdir).join("usr/bin/pi-backend");
+                if candidate.exists() {
+                    return Some(shell.command(candidate));
+                }
+            }
+        }
+    }
+
+    shell.sidecar("pi-backend").ok()
+}
+
+fn spawn_backend(app: &tauri::AppHandle, port: u16) -> Option<BackendProcess> {
+    // Dev mode: run local Python backend script directly.
     if cfg!(debug_assertions) {
         let py_venv = PathBuf::from("../../../.venv/bin/python");
         let py = if py_venv.exists() {
@@ -15,31 +143,403 @@ fn spawn_backend() -> Option<Child> {
         };
 
         let mut cmd = Command::new(py);
-        cmd.arg("../../backend/run_backend.py");
-        return cmd.spawn().ok();
+        cmd.arg("../../backend/run_backend.py")
+            .arg("--port")
+            .arg(port.to_string())
+            .stdout(Stdio::piped());
+        let mut child = cmd.spawn().ok()?;
+        let stdout = child.stdout.take()?;
+
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || forward_stdout_lines(BufReader::new(stdout), Some(tx)));
+
+        return Some(BackendProcess {
+            child: BackendChild::Std(child),
+            first_stdout_line: rx,
+            sidecar_exited: Arc::new(AtomicBool::new(false)),
+        });
+    }
+
+    // Packaged mode: launch the bundled sidecar through Tauri's resource
+    // resolver instead of guessing a path next to the current executable.
+    // This also gives us structured stdout/stderr/termination events
+    // instead of a raw pipe.
+    let (mut events, child) = resolve_sidecar_command(app)?
+        .args(["--port", &port.to_string()])
+        .spawn()
+        .ok()?;
+
+    let (tx, rx) = mpsc::channel();
+    let sidecar_exited = Arc::new(AtomicBool::new(false));
+    {
+        let sidecar_exited = Arc::clone(&sidecar_exited);
+        let mut tx = Some(tx);
+        std::thread::spawn(move || {
+            while let Some(event) = tauri::async_runtime::block_on(events.recv()) {
+                match event {
+                    CommandEvent::Stdout(line) => {
+                        println!("{line}");
+                        if let Some(sender) = tx.take() {
+                            let _ = sender.send(line);
+                        }
+                    }
+                    CommandEvent::Stderr(line) => {
+                        println!("{line}");
+                    }
+                    CommandEvent::Terminated(_) => {
+                        sidecar_exited.store(true, Ordering::SeqCst);
+                        return;
+                    }
+                    CommandEvent::Error(err) => {
+                        // A stream read error doesn't mean the process has
+                        // died — keep listening for the `Terminated` event
+                        // that actually reports its exit, rather than
+                        // declaring it dead and letting the supervisor spawn
+                        // a second instance against the same port while this
+                        // one is still alive and untracked.
+                        eprintln!("project-initializer: sidecar stream error: {err}");
+                    }
+                    _ => {}
+                }
+            }
+            // The event channel itself closed, which only happens once the
+            // sidecar process and its stdio pipes are gone.
+            sidecar_exited.store(true, Ordering::SeqCst);
+        });
     }
 
-    // Packaged mode: try sidecar binary copied into resources.
-    let exe = std::env::current_exe().ok()?;
-    let mut sidecar_path = PathBuf::from(exe.parent()?);
-    sidecar_path.push("pi-backend");
-    Command::new(sidecar_path).spawn().ok()
+    Some(BackendProcess {
+        child: BackendChild::Sidecar(Some(child)),
+        first_stdout_line: rx,
+        sidecar_exited,
+    })
+}
+
+/// Extracts the port from a backend announcement line, expected to end in
+/// its listening URL (e.g. `Listening on http://127.0.0.1:8821`). Returns
+/// `None` if the line doesn't end in a parseable port.
+fn parse_announced_port(line: &str) -> Option<u16> {
+    line.trim()
+        .rsplit(':')
+        .next()
+        .and_then(|tail| tail.trim().parse::<u16>().ok())
+}
+
+/// Reads the backend's first announced stdout line and extracts the port it
+/// actually bound, per `parse_announced_port`. Falls back to `requested` if
+/// no line arrives within `timeout` or no port can be parsed out of it.
+fn read_announced_port_with_timeout(
+    first_stdout_line: &Receiver<String>,
+    requested: u16,
+    timeout: Duration,
+) -> u16 {
+    first_stdout_line
+        .recv_timeout(timeout)
+        .ok()
+        .and_then(|line| parse_announced_port(&line))
+        .unwrap_or(requested)
+}
+
+/// Reads the backend's first announced stdout line, which is expected to
+/// contain its listening URL (e.g. `Listening on http://127.0.0.1:8821`),
+/// and extracts the port it actually bound. Falls back to `requested` if no
+/// line arrives within the handshake timeout or no port can be parsed out
+/// of it.
+fn read_announced_port(process: &BackendProcess, requested: u16) -> u16 {
+    read_announced_port_with_timeout(&process.first_stdout_line, requested, Duration::from_secs(5))
+}
+
+/// Polls the backend's port with exponential backoff until it accepts
+/// connections, or `READY_TOTAL_TIMEOUT` elapses. Returns `true` once the
+/// backend is ready to serve requests.
+fn wait_for_backend_ready(port: u16) -> bool {
+    let deadline = Instant::now() + READY_TOTAL_TIMEOUT;
+    let mut backoff = READY_INITIAL_BACKOFF;
+
+    loop {
+        if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+            return true;
+        }
+
+        if Instant::now() >= deadline {
+            return false;
+        }
+
+        std::thread::sleep(backoff);
+        backoff = std::cmp::min(backoff * 2, READY_MAX_BACKOFF);
+    }
+}
+
+fn show_startup_error(app: &tauri::AppHandle, message: &str) {
+    eprintln!("project-initializer: {message}");
+    app.dialog()
+        .message(message)
+        .title("project-initializer")
+        .blocking_show();
+}
+
+/// Watches the backend child on a dedicated thread and restarts it if it
+/// exits unexpectedly, up to `MAX_RESTARTS_PER_WINDOW` restarts within
+/// `RESTART_WINDOW`. Emits `backend://restarted` after each successful
+/// restart and `backend://failed` once restarts are exhausted or the
+/// backend can't be respawned at all. Polls rather than blocking so the
+/// shutdown path can still lock `backend` to kill it without racing the
+/// supervisor.
+fn supervise_backend(
+    app: tauri::AppHandle,
+    backend: BackendHandle,
+    port: u16,
+    shutting_down: Arc<AtomicBool>,
+) {
+    std::thread::spawn(move || {
+        let mut restart_times: Vec<Instant> = Vec::new();
+
+        loop {
+            std::thread::sleep(SUPERVISOR_POLL_INTERVAL);
+            if shutting_down.load(Ordering::SeqCst) {
+                return;
+            }
+
+            let exited = {
+                let mut guard = backend.lock().unwrap();
+                match guard.as_mut() {
+                    Some(process) => process.has_exited(),
+                    None => return,
+                }
+            };
+            if !exited {
+                continue;
+            }
+
+            let now = Instant::now();
+            restart_times.retain(|t| now.duration_since(*t) < RESTART_WINDOW);
+            if restart_times.len() >= MAX_RESTARTS_PER_WINDOW {
+                *backend.lock().unwrap() = None;
+                let _ = app.emit("backend://failed", ());
+                return;
+            }
+
+            // Back off before respawning, doubling with each restart still
+            // inside the current window so a backend that crashes
+            // immediately on launch doesn't just burn through all of its
+            // restarts in a tight loop.
+            let mut backoff = RESTART_INITIAL_BACKOFF;
+            for _ in 0..restart_times.len() {
+                backoff = std::cmp::min(backoff * 2, RESTART_MAX_BACKOFF);
+            }
+            std::thread::sleep(backoff);
+            if shutting_down.load(Ordering::SeqCst) {
+                return;
+            }
+
+            match spawn_backend(&app, port) {
+                Some(mut new_process) => {
+                    // The app may have started shutting down while we were
+                    // spawning above. Re-check under the same lock the
+                    // shutdown path kills through, so the check and the
+                    // install can't straddle a shutdown in between: either
+                    // shutdown hasn't locked yet (we install first, and its
+                    // kill() then reaches this new process), or it already
+                    // has (we see `shutting_down` and kill it ourselves
+                    // instead of handing the app a child nobody will ever
+                    // kill).
+                    let mut guard = backend.lock().unwrap();
+                    if shutting_down.load(Ordering::SeqCst) {
+                        new_process.kill();
+                        return;
+                    }
+                    restart_times.push(now);
+                    *guard = Some(new_process);
+                    drop(guard);
+                    let _ = app.emit("backend://restarted", restart_times.len());
+                }
+                None => {
+                    *backend.lock().unwrap() = None;
+                    let _ = app.emit("backend://failed", ());
+                    return;
+                }
+            }
+        }
+    });
+}
+
+/// Resolved backend base URL, made available to the frontend via
+/// `invoke("backend_url")`. Kept as managed state (rather than baked into
+/// a JS injection) so it stays queryable — and correct — across backend
+/// restarts.
+struct ApiBase {
+    port: u16,
+}
+
+/// Resolves the base URL the frontend should talk to. Honors a
+/// `PI_API_BASE` or `PUBLIC_BACKEND_URL` env override (for pointing at a
+/// remote or already-running dev backend, `PI_API_BASE` taking precedence
+/// if both are set) before falling back to the dynamically chosen local
+/// port.
+fn resolve_backend_url(port: u16) -> String {
+    if let Ok(url) = std::env::var("PI_API_BASE").or_else(|_| std::env::var("PUBLIC_BACKEND_URL"))
+    {
+        return url;
+    }
+    format!("http://127.0.0.1:{port}")
+}
+
+/// Returns the base URL the frontend should talk to, via
+/// `invoke("backend_url")`. See `resolve_backend_url` for the resolution
+/// rules.
+#[tauri::command]
+fn backend_url(state: tauri::State<ApiBase>) -> String {
+    resolve_backend_url(state.port)
 }
 
 fn main() {
-    let mut backend = spawn_backend();
+    // Build (but don't yet run) the app so a real `AppHandle` exists before
+    // we spawn the backend — the sidecar resolver needs it to reach the
+    // shell plugin.
+    let app = tauri::Builder::default()
+        .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_dialog::init())
+        .invoke_handler(tauri::generate_handler![backend_url])
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application");
+    let app_handle = app.handle().clone();
 
-    tauri::Builder::default()
-        .setup(|app| {
-            if let Some(win) = app.get_webview_window("main") {
-                win.eval("window.__PI_API_BASE = 'http://127.0.0.1:8787';").ok();
+    let requested_port = pick_free_port();
+    let mut process = spawn_backend(&app_handle, requested_port);
+    let port = process
+        .as_ref()
+        .map(|process| read_announced_port(process, requested_port))
+        .unwrap_or(requested_port);
+
+    if !wait_for_backend_ready(port) {
+        show_startup_error(
+            &app_handle,
+            "The backend did not become ready in time. The application will now exit.",
+        );
+        if let Some(process) = process.as_mut() {
+            process.kill();
+        }
+        std::process::exit(1);
+    }
+
+    app_handle.manage(ApiBase { port });
+
+    let backend: BackendHandle = Arc::new(Mutex::new(process));
+    let shutting_down = Arc::new(AtomicBool::new(false));
+    supervise_backend(
+        app_handle,
+        Arc::clone(&backend),
+        port,
+        Arc::clone(&shutting_down),
+    );
+
+    app.run(move |_app_handle, event| {
+        if let tauri::RunEvent::ExitRequested { .. } | tauri::RunEvent::Exit = event {
+            shutting_down.store(true, Ordering::SeqCst);
+            if let Some(process) = backend.lock().unwrap().as_mut() {
+                process.kill();
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `resolve_backend_url` tests mutate process-wide env vars, so they're
+    // serialized against each other (and against any other test touching
+    // these vars) through this lock.
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    fn with_env_override<T>(vars: &[(&str, Option<&str>)], f: impl FnOnce() -> T) -> T {
+        let _guard = ENV_LOCK.lock().unwrap();
+        for (key, value) in vars {
+            match value {
+                Some(value) => std::env::set_var(key, value),
+                None => std::env::remove_var(key),
             }
-            Ok(())
-        })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        }
+        let result = f();
+        for (key, _) in vars {
+            std::env::remove_var(key);
+        }
+        result
+    }
+
+    #[test]
+    fn resolve_backend_url_falls_back_to_local_port_without_override() {
+        with_env_override(&[("PI_API_BASE", None), ("PUBLIC_BACKEND_URL", None)], || {
+            assert_eq!(resolve_backend_url(8821), "http://127.0.0.1:8821");
+        });
+    }
+
+    #[test]
+    fn resolve_backend_url_honors_pi_api_base() {
+        with_env_override(
+            &[
+                ("PI_API_BASE", Some("https://example.test")),
+                ("PUBLIC_BACKEND_URL", None),
+            ],
+            || {
+                assert_eq!(resolve_backend_url(8821), "https://example.test");
+            },
+        );
+    }
+
+    #[test]
+    fn resolve_backend_url_honors_public_backend_url() {
+        with_env_override(
+            &[
+                ("PI_API_BASE", None),
+                ("PUBLIC_BACKEND_URL", Some("https://public.test")),
+            ],
+            || {
+                assert_eq!(resolve_backend_url(8821), "https://public.test");
+            },
+        );
+    }
+
+    #[test]
+    fn resolve_backend_url_prefers_pi_api_base_when_both_set() {
+        with_env_override(
+            &[
+                ("PI_API_BASE", Some("https://example.test")),
+                ("PUBLIC_BACKEND_URL", Some("https://public.test")),
+            ],
+            || {
+                assert_eq!(resolve_backend_url(8821), "https://example.test");
+            },
+        );
+    }
+
+    #[test]
+    fn read_announced_port_parses_happy_path() {
+        let (tx, rx) = mpsc::channel();
+        tx.send("Listening on http://127.0.0.1:8821".to_string())
+            .unwrap();
+        assert_eq!(
+            read_announced_port_with_timeout(&rx, 9999, Duration::from_millis(50)),
+            8821
+        );
+    }
+
+    #[test]
+    fn read_announced_port_falls_back_on_garbage_first_line() {
+        let (tx, rx) = mpsc::channel();
+        tx.send("backend starting up...".to_string()).unwrap();
+        assert_eq!(
+            read_announced_port_with_timeout(&rx, 9999, Duration::from_millis(50)),
+            9999
+        );
+    }
 
-    if let Some(child) = backend.as_mut() {
-        let _ = child.kill();
+    #[test]
+    fn read_announced_port_falls_back_on_timeout() {
+        let (_tx, rx) = mpsc::channel::<String>();
+        assert_eq!(
+            read_announced_port_with_timeout(&rx, 9999, Duration::from_millis(50)),
+            9999
+        );
     }
 }